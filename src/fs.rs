@@ -1,3 +1,4 @@
+use rayon::prelude::*;
 use std::fs::DirEntry;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
@@ -22,15 +23,57 @@ pub struct Folder {
 
 #[derive(Debug)]
 pub enum File {
-    Direct(PathBuf),
+    /// A file that exists on disk and will be read in when the archive is built.
+    Direct { name: String, source: PathBuf },
     Virtual {
         name: String,
         offset: u64,
         length: u32,
         checksum: u32,
+        /// The codec the file's bytes are stored with in the data file.
+        codec: crate::io::Codec,
+        /// The file's decompressed size, equal to `length` when `codec` is `Codec::None`.
+        uncompressed_length: u32,
     },
 }
 
+impl File {
+    /// Returns the file's name within the archive.
+    pub fn name(&self) -> &str {
+        match self {
+            File::Direct { name, .. } => name,
+            File::Virtual { name, .. } => name,
+        }
+    }
+}
+
+/// The result of checking a single file's CRC-32 checksum, returned by `Filesystem::verify`.
+#[derive(Debug, Clone)]
+pub struct VerifyResult {
+    /// The file's path, relative to the archive root.
+    pub path: String,
+    /// The checksum recorded in the archive header.
+    pub expected_checksum: u32,
+    /// The checksum recomputed from the file's bytes in the data file.
+    pub actual_checksum: u32,
+    /// Whether `expected_checksum` and `actual_checksum` match.
+    pub matches: bool,
+    /// The recomputed MD5 digest, as a lowercase hex string, if requested.
+    pub md5: Option<String>,
+    /// The recomputed SHA-1 digest, as a lowercase hex string, if requested.
+    pub sha1: Option<String>,
+}
+
+/// A virtual file's path and location within the data file, collected while walking the
+/// directory tree for `Filesystem::verify`.
+struct VirtualFileTarget {
+    path: String,
+    offset: u64,
+    length: u32,
+    checksum: u32,
+    codec: crate::io::Codec,
+}
+
 #[derive(Error, Debug)]
 pub enum FilesystemError {
     #[error("specified path is not a directory {0}")]
@@ -39,21 +82,47 @@ pub enum FilesystemError {
     NotAFile(PathBuf),
     #[error("invalid magic value {0}")]
     InvalidMagicValue(String),
+    #[error("file is not part of an archive {0}")]
+    NotAVirtualFile(PathBuf),
+    #[error("path not found in archive: {0}")]
+    PathNotFound(String),
+    #[error("path already exists in archive: {0}")]
+    PathAlreadyExists(String),
+    #[error("unknown codec tag {0}")]
+    UnknownCodec(u8),
+    #[error("unsupported header version {0}")]
+    UnsupportedHeaderVersion(u32),
+    #[error("data part {0} not found")]
+    PartNotFound(u32),
+    #[error("expected to read {expected} bytes at offset {offset}, but only {actual} were available")]
+    TruncatedRead { offset: u64, expected: u32, actual: u64 },
 }
 
 impl Filesystem {
-    /// Initialises a Shaiya filesystem from an existing archive.
+    /// Initialises a Shaiya filesystem from an existing archive, decoding filenames as EUC-KR.
     ///
     /// # Arguments
     /// * `header_path`    - The path to the header.
     pub fn from_archive(header_path: &Path) -> anyhow::Result<Self> {
+        Self::from_archive_with_encoding(header_path, crate::io::TextEncoding::default())
+    }
+
+    /// Initialises a Shaiya filesystem from an existing archive.
+    ///
+    /// # Arguments
+    /// * `header_path`    - The path to the header.
+    /// * `encoding`       - The text encoding used to decode filenames.
+    pub fn from_archive_with_encoding(
+        header_path: &Path,
+        encoding: crate::io::TextEncoding,
+    ) -> anyhow::Result<Self> {
         let metadata = header_path.metadata()?;
         if !metadata.is_file() {
             return Err(FilesystemError::NotAFile(header_path.into()).into());
         }
 
         let data = std::fs::read(header_path)?;
-        crate::io::read_filesystem(Cursor::new(data.as_slice()))
+        crate::io::read_filesystem(Cursor::new(data.as_slice()), encoding)
     }
 
     /// Opens a Shaiya filesystem from a path found on disk.
@@ -74,17 +143,43 @@ impl Filesystem {
         Ok(Self { contents })
     }
 
-    /// Builds the virtual filesystem to temporary files.
+    /// Builds the virtual filesystem to temporary files, encoding filenames as EUC-KR and leaving
+    /// file contents uncompressed.
     pub fn build(&self) -> anyhow::Result<(std::fs::File, std::fs::File)> {
+        self.build_with_options(crate::io::TextEncoding::default(), crate::io::Codec::default())
+    }
+
+    /// Builds the virtual filesystem to temporary files, leaving file contents uncompressed.
+    ///
+    /// # Arguments
+    /// * `encoding`    - The text encoding used to encode filenames.
+    pub fn build_with_encoding(
+        &self,
+        encoding: crate::io::TextEncoding,
+    ) -> anyhow::Result<(std::fs::File, std::fs::File)> {
+        self.build_with_options(encoding, crate::io::Codec::default())
+    }
+
+    /// Builds the virtual filesystem to temporary files.
+    ///
+    /// # Arguments
+    /// * `encoding`    - The text encoding used to encode filenames.
+    /// * `codec`       - The codec used to store file contents; `Codec::None` is the legacy, fully-compatible format, while `Codec::Zstd` opts into the extended format and compresses every file.
+    pub fn build_with_options(
+        &self,
+        encoding: crate::io::TextEncoding,
+        codec: crate::io::Codec,
+    ) -> anyhow::Result<(std::fs::File, std::fs::File)> {
         let mut header_file = tempfile::tempfile()?;
         let mut data_file = tempfile::tempfile()?;
 
-        crate::io::build_filesystem(self, &mut header_file, &mut data_file)?;
+        crate::io::build_filesystem(self, &mut header_file, &mut data_file, encoding, codec)?;
 
         Ok((header_file, data_file))
     }
 
-    /// Builds the virtual filesystem, to specified files.
+    /// Builds the virtual filesystem, to specified files, encoding filenames as EUC-KR and
+    /// leaving file contents uncompressed.
     ///
     /// # Arguments
     /// * `header`  - The destination header file.
@@ -94,7 +189,272 @@ impl Filesystem {
         header: &mut std::fs::File,
         data: &mut std::fs::File,
     ) -> anyhow::Result<()> {
-        crate::io::build_filesystem(self, header, data)
+        crate::io::build_filesystem(
+            self,
+            header,
+            data,
+            crate::io::TextEncoding::default(),
+            crate::io::Codec::default(),
+        )
+    }
+
+    /// Builds the virtual filesystem to a header file and a data payload split across
+    /// sequentially-numbered parts, encoding filenames as EUC-KR and leaving file contents
+    /// uncompressed.
+    ///
+    /// # Arguments
+    /// * `base_data_path`  - The path of the first data part (e.g. `data.saf`); subsequent parts are named `data.001`, `data.002`, and so on, alongside it.
+    /// * `header`          - The destination file for the header.
+    /// * `part_size`       - The maximum number of bytes written to each part before rolling to a new one.
+    pub fn build_split(
+        &self,
+        base_data_path: &Path,
+        header: &mut std::fs::File,
+        part_size: u64,
+    ) -> anyhow::Result<()> {
+        self.build_split_with_options(
+            base_data_path,
+            header,
+            part_size,
+            crate::io::TextEncoding::default(),
+            crate::io::Codec::default(),
+        )
+    }
+
+    /// Builds the virtual filesystem to a header file and a data payload split across
+    /// sequentially-numbered parts.
+    ///
+    /// # Arguments
+    /// * `base_data_path`  - The path of the first data part (e.g. `data.saf`); subsequent parts are named `data.001`, `data.002`, and so on, alongside it.
+    /// * `header`          - The destination file for the header.
+    /// * `part_size`       - The maximum number of bytes written to each part before rolling to a new one.
+    /// * `encoding`        - The text encoding used to encode filenames.
+    /// * `codec`           - The codec used to store file contents.
+    pub fn build_split_with_options(
+        &self,
+        base_data_path: &Path,
+        header: &mut std::fs::File,
+        part_size: u64,
+        encoding: crate::io::TextEncoding,
+        codec: crate::io::Codec,
+    ) -> anyhow::Result<()> {
+        crate::io::build_filesystem_split(self, base_data_path, header, part_size, encoding, codec)
+    }
+
+    /// Extracts every file in the archive to disk, recreating the folder tree at `dest_dir`.
+    ///
+    /// # Arguments
+    /// * `saf_path`    - The path to the paired `data.saf` file.
+    /// * `dest_dir`    - The directory to extract the contents into.
+    pub fn extract_all(&self, saf_path: &Path, dest_dir: &Path) -> anyhow::Result<()> {
+        let mut reader = crate::io::SafReader::open(saf_path)?;
+        Self::extract_contents(&self.contents, dest_dir, &mut reader)
+    }
+
+    /// Extracts every file in the archive to disk, recreating the folder tree at `dest_dir`, where
+    /// the data payload is split across sequentially-numbered parts. The part boundaries are
+    /// derived from each part's size on disk, so the `part_size` used to build the archive does not
+    /// need to be supplied here.
+    ///
+    /// # Arguments
+    /// * `base_data_path`  - The path of the first data part (e.g. `data.saf`).
+    /// * `dest_dir`        - The directory to extract the contents into.
+    pub fn extract_all_split(&self, base_data_path: &Path, dest_dir: &Path) -> anyhow::Result<()> {
+        let mut reader = crate::io::SplitSafReader::open(base_data_path)?;
+        Self::extract_contents_split(&self.contents, dest_dir, &mut reader)
+    }
+
+    /// Verifies every file's recorded CRC-32 checksum against its bytes in the data file.
+    ///
+    /// # Arguments
+    /// * `saf_path`    - The path to the paired `data.saf` file.
+    /// * `opts`        - Which additional digests to compute, and whether to verify concurrently.
+    pub fn verify(&self, saf_path: &Path, opts: crate::io::VerifyOptions) -> anyhow::Result<Vec<VerifyResult>> {
+        let mut targets = Vec::new();
+        Self::collect_virtual_files(&self.contents, &PathBuf::new(), &mut targets)?;
+
+        if opts.parallel {
+            targets
+                .into_par_iter()
+                .map(|target| {
+                    let mut reader = crate::io::SafReader::open(saf_path)?;
+                    Self::verify_one(&mut reader, target, opts)
+                })
+                .collect()
+        } else {
+            let mut reader = crate::io::SafReader::open(saf_path)?;
+            targets
+                .into_iter()
+                .map(|target| Self::verify_one(&mut reader, target, opts))
+                .collect()
+        }
+    }
+
+    /// Verifies every file's recorded CRC-32 checksum against its bytes in a data payload split
+    /// across sequentially-numbered parts.
+    ///
+    /// # Arguments
+    /// * `base_data_path`  - The path of the first data part (e.g. `data.saf`).
+    /// * `opts`            - Which additional digests to compute, and whether to verify concurrently.
+    pub fn verify_split(
+        &self,
+        base_data_path: &Path,
+        opts: crate::io::VerifyOptions,
+    ) -> anyhow::Result<Vec<VerifyResult>> {
+        let mut targets = Vec::new();
+        Self::collect_virtual_files(&self.contents, &PathBuf::new(), &mut targets)?;
+
+        if opts.parallel {
+            targets
+                .into_par_iter()
+                .map(|target| {
+                    let mut reader = crate::io::SplitSafReader::open(base_data_path)?;
+                    Self::verify_one_split(&mut reader, target, opts)
+                })
+                .collect()
+        } else {
+            let mut reader = crate::io::SplitSafReader::open(base_data_path)?;
+            targets
+                .into_iter()
+                .map(|target| Self::verify_one_split(&mut reader, target, opts))
+                .collect()
+        }
+    }
+
+    /// Recomputes a single file's checksum (and any requested digests) and compares it against
+    /// the expected value recorded in the header.
+    ///
+    /// # Arguments
+    /// * `reader`  - The reader used to pull the file's bytes out of the data file.
+    /// * `target`  - The file's path and its recorded offset, length and checksum.
+    /// * `opts`    - Which additional digests to compute.
+    fn verify_one(
+        reader: &mut crate::io::SafReader,
+        target: VirtualFileTarget,
+        opts: crate::io::VerifyOptions,
+    ) -> anyhow::Result<VerifyResult> {
+        let VirtualFileTarget { path, offset, length, checksum, codec } = target;
+        let digest = reader.verify_file(offset, length, codec, opts)?;
+
+        Ok(VerifyResult {
+            path,
+            expected_checksum: checksum,
+            actual_checksum: digest.checksum,
+            matches: digest.checksum == checksum,
+            md5: digest.md5,
+            sha1: digest.sha1,
+        })
+    }
+
+    /// Recomputes a single file's checksum (and any requested digests) from a split data payload
+    /// and compares it against the expected value recorded in the header.
+    ///
+    /// # Arguments
+    /// * `reader`  - The reader used to pull the file's bytes out of the split data payload.
+    /// * `target`  - The file's path and its recorded offset, length and checksum.
+    /// * `opts`    - Which additional digests to compute.
+    fn verify_one_split(
+        reader: &mut crate::io::SplitSafReader,
+        target: VirtualFileTarget,
+        opts: crate::io::VerifyOptions,
+    ) -> anyhow::Result<VerifyResult> {
+        let VirtualFileTarget { path, offset, length, checksum, codec } = target;
+        let digest = reader.verify_file(offset, length, codec, opts)?;
+
+        Ok(VerifyResult {
+            path,
+            expected_checksum: checksum,
+            actual_checksum: digest.checksum,
+            matches: digest.checksum == checksum,
+            md5: digest.md5,
+            sha1: digest.sha1,
+        })
+    }
+
+    /// Recursively collects the path, offset, length and checksum of every virtual file in a
+    /// directory tree.
+    ///
+    /// # Arguments
+    /// * `contents`    - The directory contents.
+    /// * `prefix`      - The path of the directory, relative to the archive root.
+    /// * `out`         - The collected targets.
+    fn collect_virtual_files(
+        contents: &[DirectoryEntry],
+        prefix: &Path,
+        out: &mut Vec<VirtualFileTarget>,
+    ) -> anyhow::Result<()> {
+        for entry in contents {
+            match entry {
+                DirectoryEntry::File(File::Virtual { name, offset, length, checksum, codec, .. }) => {
+                    out.push(VirtualFileTarget {
+                        path: prefix.join(name).to_string_lossy().into_owned(),
+                        offset: *offset,
+                        length: *length,
+                        checksum: *checksum,
+                        codec: *codec,
+                    });
+                }
+                DirectoryEntry::File(File::Direct { source, .. }) => {
+                    return Err(FilesystemError::NotAVirtualFile(source.clone()).into());
+                }
+                DirectoryEntry::Folder(folder) => {
+                    Self::collect_virtual_files(&folder.contents, &prefix.join(&folder.name), out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Recursively extracts a directory's contents to disk.
+    ///
+    /// # Arguments
+    /// * `contents`    - The directory contents.
+    /// * `dest_dir`    - The directory to extract the contents into.
+    /// * `reader`      - The reader used to pull file bytes out of the data file.
+    fn extract_contents(
+        contents: &[DirectoryEntry],
+        dest_dir: &Path,
+        reader: &mut crate::io::SafReader,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dest_dir)?;
+        for entry in contents {
+            match entry {
+                DirectoryEntry::File(file) => {
+                    let mut dest_file = std::fs::File::create(dest_dir.join(file.name()))?;
+                    reader.read_file_into(file, &mut dest_file)?;
+                }
+                DirectoryEntry::Folder(folder) => {
+                    Self::extract_contents(&folder.contents, &dest_dir.join(&folder.name), reader)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Recursively extracts a directory's contents to disk, reading from a split data payload.
+    ///
+    /// # Arguments
+    /// * `contents`    - The directory contents.
+    /// * `dest_dir`    - The directory to extract the contents into.
+    /// * `reader`      - The reader used to pull file bytes out of the split data payload.
+    fn extract_contents_split(
+        contents: &[DirectoryEntry],
+        dest_dir: &Path,
+        reader: &mut crate::io::SplitSafReader,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dest_dir)?;
+        for entry in contents {
+            match entry {
+                DirectoryEntry::File(file) => {
+                    let mut dest_file = std::fs::File::create(dest_dir.join(file.name()))?;
+                    reader.read_file_into(file, &mut dest_file)?;
+                }
+                DirectoryEntry::Folder(folder) => {
+                    Self::extract_contents_split(&folder.contents, &dest_dir.join(&folder.name), reader)?;
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Maps an directory entry on disk, do a virtual filesystem entry.
@@ -107,7 +467,7 @@ impl Filesystem {
             let name: String = entry
                 .path()
                 .components()
-                .last()
+                .next_back()
                 .unwrap()
                 .as_os_str()
                 .to_string_lossy()
@@ -118,6 +478,344 @@ impl Filesystem {
             return Ok(DirectoryEntry::Folder(Folder { name, contents }));
         }
 
-        Ok(DirectoryEntry::File(File::Direct(entry.path())))
+        let name = entry.path().file_name().unwrap().to_string_lossy().into_owned();
+        Ok(DirectoryEntry::File(File::Direct { name, source: entry.path() }))
+    }
+
+    /// Adds a new file to the archive at `path`, read from `source` when the archive is next
+    /// built.
+    ///
+    /// # Arguments
+    /// * `path`    - The file's path within the archive.
+    /// * `source`  - The path to the file's contents on disk.
+    pub fn add_file(&mut self, path: &str, source: PathBuf) -> anyhow::Result<()> {
+        let (dir_path, name) = Self::split_path(path)?;
+        let dir = Self::navigate_mut(&mut self.contents, &dir_path)?;
+
+        if dir.iter().any(|e| Self::entry_name(e) == name) {
+            return Err(FilesystemError::PathAlreadyExists(path.to_string()).into());
+        }
+
+        dir.push(DirectoryEntry::File(File::Direct { name: name.to_string(), source }));
+        Ok(())
+    }
+
+    /// Replaces an existing file's contents at `path`, read from `source` when the archive is
+    /// next built.
+    ///
+    /// # Arguments
+    /// * `path`    - The file's path within the archive.
+    /// * `source`  - The path to the file's new contents on disk.
+    pub fn replace_file(&mut self, path: &str, source: PathBuf) -> anyhow::Result<()> {
+        let (dir_path, name) = Self::split_path(path)?;
+        let dir = Self::navigate_mut(&mut self.contents, &dir_path)?;
+
+        let index = dir
+            .iter()
+            .position(|e| matches!(e, DirectoryEntry::File(f) if f.name() == name))
+            .ok_or_else(|| FilesystemError::PathNotFound(path.to_string()))?;
+
+        if dir.iter().enumerate().any(|(i, e)| i != index && Self::entry_name(e) == name) {
+            return Err(FilesystemError::PathAlreadyExists(path.to_string()).into());
+        }
+
+        dir[index] = DirectoryEntry::File(File::Direct { name: name.to_string(), source });
+        Ok(())
+    }
+
+    /// Removes the file at `path`.
+    ///
+    /// # Arguments
+    /// * `path`    - The file's path within the archive.
+    pub fn remove_file(&mut self, path: &str) -> anyhow::Result<()> {
+        let (dir_path, name) = Self::split_path(path)?;
+        let dir = Self::navigate_mut(&mut self.contents, &dir_path)?;
+
+        let index = dir
+            .iter()
+            .position(|e| matches!(e, DirectoryEntry::File(f) if f.name() == name))
+            .ok_or_else(|| FilesystemError::PathNotFound(path.to_string()))?;
+
+        dir.remove(index);
+        Ok(())
+    }
+
+    /// Renames the file or folder at `path` to `new_name`.
+    ///
+    /// # Arguments
+    /// * `path`        - The file or folder's path within the archive.
+    /// * `new_name`    - The entry's new name.
+    pub fn rename(&mut self, path: &str, new_name: &str) -> anyhow::Result<()> {
+        let (dir_path, name) = Self::split_path(path)?;
+        let dir = Self::navigate_mut(&mut self.contents, &dir_path)?;
+
+        let index = dir
+            .iter()
+            .position(|e| Self::entry_name(e) == name)
+            .ok_or_else(|| FilesystemError::PathNotFound(path.to_string()))?;
+
+        if dir.iter().enumerate().any(|(i, e)| i != index && Self::entry_name(e) == new_name) {
+            return Err(FilesystemError::PathAlreadyExists(new_name.to_string()).into());
+        }
+
+        match &mut dir[index] {
+            DirectoryEntry::Folder(folder) => folder.name = new_name.to_string(),
+            DirectoryEntry::File(File::Direct { name, .. }) => *name = new_name.to_string(),
+            DirectoryEntry::File(File::Virtual { name, .. }) => *name = new_name.to_string(),
+        }
+        Ok(())
+    }
+
+    /// Splits an archive path into its parent directory's components and the final entry name.
+    ///
+    /// # Arguments
+    /// * `path`    - The archive path, using `/` as a separator.
+    fn split_path(path: &str) -> anyhow::Result<(Vec<&str>, &str)> {
+        let mut components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let name = components
+            .pop()
+            .ok_or_else(|| FilesystemError::PathNotFound(path.to_string()))?;
+        Ok((components, name))
+    }
+
+    /// Returns the name of a directory entry, whether it's a file or a folder.
+    ///
+    /// # Arguments
+    /// * `entry`   - The directory entry.
+    fn entry_name(entry: &DirectoryEntry) -> &str {
+        match entry {
+            DirectoryEntry::Folder(folder) => &folder.name,
+            DirectoryEntry::File(file) => file.name(),
+        }
+    }
+
+    /// Navigates to the directory contents found at `components`, relative to `contents`.
+    ///
+    /// # Arguments
+    /// * `contents`    - The directory contents to navigate from.
+    /// * `components`  - The path components of the folder to navigate to.
+    fn navigate_mut<'a>(
+        contents: &'a mut Vec<DirectoryEntry>,
+        components: &[&str],
+    ) -> anyhow::Result<&'a mut Vec<DirectoryEntry>> {
+        let Some((head, rest)) = components.split_first() else {
+            return Ok(contents);
+        };
+
+        let folder = contents.iter_mut().find_map(|entry| match entry {
+            DirectoryEntry::Folder(folder) if folder.name == *head => Some(folder),
+            _ => None,
+        });
+
+        match folder {
+            Some(folder) => Self::navigate_mut(&mut folder.contents, rest),
+            None => Err(FilesystemError::PathNotFound(components.join("/")).into()),
+        }
+    }
+
+    /// Rebuilds an archive, streaming untouched virtual files straight from the old data file
+    /// and appending new direct files, without needing to fully unpack and repack the archive.
+    /// Newly-added direct files are stored uncompressed; virtual files keep whichever codec they
+    /// were already stored with.
+    ///
+    /// # Arguments
+    /// * `old_saf`     - The path to the existing `data.saf` file backing this filesystem's virtual entries.
+    /// * `new_header`  - The destination file for the rebuilt header.
+    /// * `new_saf`     - The destination file for the rebuilt data.
+    pub fn rebuild_from(
+        &self,
+        old_saf: &Path,
+        new_header: &mut std::fs::File,
+        new_saf: &mut std::fs::File,
+    ) -> anyhow::Result<()> {
+        self.rebuild_from_with_codec(old_saf, new_header, new_saf, crate::io::Codec::default())
+    }
+
+    /// Rebuilds an archive, streaming untouched virtual files straight from the old data file
+    /// and appending new direct files, without needing to fully unpack and repack the archive.
+    ///
+    /// # Arguments
+    /// * `old_saf`     - The path to the existing `data.saf` file backing this filesystem's virtual entries.
+    /// * `new_header`  - The destination file for the rebuilt header.
+    /// * `new_saf`     - The destination file for the rebuilt data.
+    /// * `codec`       - The codec used to store any newly-added direct files.
+    pub fn rebuild_from_with_codec(
+        &self,
+        old_saf: &Path,
+        new_header: &mut std::fs::File,
+        new_saf: &mut std::fs::File,
+        codec: crate::io::Codec,
+    ) -> anyhow::Result<()> {
+        crate::io::rebuild_filesystem(self, old_saf, new_header, new_saf, crate::io::TextEncoding::default(), codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an archive split into small parts, with one file straddling a part boundary and
+    /// another bigger than a whole part, then extracts it back and checks the bytes round-trip.
+    #[test]
+    fn split_archive_round_trips_across_part_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let small = vec![1u8; 400];
+        let straddling = vec![2u8; 1_500];
+        let bigger_than_part = vec![3u8; 2_500];
+        for (name, contents) in [
+            ("small.bin", &small),
+            ("straddling.bin", &straddling),
+            ("bigger_than_part.bin", &bigger_than_part),
+        ] {
+            std::fs::write(dir.path().join(name), contents).unwrap();
+        }
+
+        let contents = vec![
+            DirectoryEntry::File(File::Direct { name: "small.bin".into(), source: dir.path().join("small.bin") }),
+            DirectoryEntry::File(File::Direct {
+                name: "straddling.bin".into(),
+                source: dir.path().join("straddling.bin"),
+            }),
+            DirectoryEntry::File(File::Direct {
+                name: "bigger_than_part.bin".into(),
+                source: dir.path().join("bigger_than_part.bin"),
+            }),
+        ];
+        let fs = Filesystem { contents };
+
+        let header_path = dir.path().join("data.sah");
+        let base_data_path = dir.path().join("data.saf");
+        let mut header_file = std::fs::File::create(&header_path).unwrap();
+        fs.build_split(&base_data_path, &mut header_file, 1_000).unwrap();
+
+        // A 400 + 1,500 + 2,500 byte payload split at 1,000 bytes per part spans five parts.
+        assert!(dir.path().join("data.004").is_file());
+        assert!(!dir.path().join("data.005").exists());
+
+        let rebuilt = Filesystem::from_archive(&header_path).unwrap();
+        let dest_dir = dir.path().join("out");
+        rebuilt.extract_all_split(&base_data_path, &dest_dir).unwrap();
+
+        assert_eq!(std::fs::read(dest_dir.join("small.bin")).unwrap(), small);
+        assert_eq!(std::fs::read(dest_dir.join("straddling.bin")).unwrap(), straddling);
+        assert_eq!(std::fs::read(dest_dir.join("bigger_than_part.bin")).unwrap(), bigger_than_part);
+    }
+
+    /// Builds an archive with a Korean filename, encoded as EUC-KR, and checks it reads back with
+    /// the same name.
+    #[test]
+    fn euc_kr_filename_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let name = "몬스터.txt";
+        std::fs::write(dir.path().join(name), b"contents").unwrap();
+
+        let contents = vec![DirectoryEntry::File(File::Direct { name: name.into(), source: dir.path().join(name) })];
+        let fs = Filesystem { contents };
+
+        let header_path = dir.path().join("data.sah");
+        let data_path = dir.path().join("data.saf");
+        let mut header_file = std::fs::File::create(&header_path).unwrap();
+        let mut data_file = std::fs::File::create(&data_path).unwrap();
+        fs.build_with_destination(&mut header_file, &mut data_file).unwrap();
+
+        let rebuilt = Filesystem::from_archive(&header_path).unwrap();
+        assert_eq!(rebuilt.contents.len(), 1);
+        let DirectoryEntry::File(file) = &rebuilt.contents[0] else { panic!("expected a file") };
+        assert_eq!(file.name(), name);
+    }
+
+    /// Builds an archive with `Codec::Zstd`, then extracts and verifies it, checking the bytes and
+    /// checksums recomputed from the compressed data match the original contents.
+    #[test]
+    fn zstd_compressed_file_extracts_and_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let name = "compressible.bin";
+        let original = vec![7u8; 64 * 1024];
+        std::fs::write(dir.path().join(name), &original).unwrap();
+
+        let contents = vec![DirectoryEntry::File(File::Direct { name: name.into(), source: dir.path().join(name) })];
+        let fs = Filesystem { contents };
+
+        let header_path = dir.path().join("data.sah");
+        let saf_path = dir.path().join("data.saf");
+        let mut header_file = std::fs::File::create(&header_path).unwrap();
+        let mut saf_file = std::fs::File::create(&saf_path).unwrap();
+        crate::io::build_filesystem(
+            &fs,
+            &mut header_file,
+            &mut saf_file,
+            crate::io::TextEncoding::default(),
+            crate::io::Codec::Zstd,
+        )
+        .unwrap();
+
+        let rebuilt = Filesystem::from_archive(&header_path).unwrap();
+
+        let dest_dir = dir.path().join("out");
+        rebuilt.extract_all(&saf_path, &dest_dir).unwrap();
+        assert_eq!(std::fs::read(dest_dir.join(name)).unwrap(), original);
+
+        let results = rebuilt
+            .verify(&saf_path, crate::io::VerifyOptions { md5: true, sha1: true, parallel: false })
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].matches);
+        assert!(results[0].md5.is_some());
+        assert!(results[0].sha1.is_some());
+    }
+
+    /// Edits a Zstd-compressed archive (adding a new file, leaving the existing one untouched) and
+    /// rebuilds it, checking the original compressed virtual file's bytes survive the rebuild.
+    #[test]
+    fn rebuild_from_preserves_compressed_virtual_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let name = "compressible.bin";
+        let original = vec![9u8; 64 * 1024];
+        std::fs::write(dir.path().join(name), &original).unwrap();
+
+        let contents = vec![DirectoryEntry::File(File::Direct { name: name.into(), source: dir.path().join(name) })];
+        let fs = Filesystem { contents };
+
+        let header_path = dir.path().join("data.sah");
+        let saf_path = dir.path().join("data.saf");
+        let mut header_file = std::fs::File::create(&header_path).unwrap();
+        let mut saf_file = std::fs::File::create(&saf_path).unwrap();
+        crate::io::build_filesystem(
+            &fs,
+            &mut header_file,
+            &mut saf_file,
+            crate::io::TextEncoding::default(),
+            crate::io::Codec::Zstd,
+        )
+        .unwrap();
+
+        let mut rebuilt = Filesystem::from_archive(&header_path).unwrap();
+
+        let new_name = "added.bin";
+        let new_contents = vec![3u8; 128];
+        std::fs::write(dir.path().join(new_name), &new_contents).unwrap();
+        rebuilt.add_file(new_name, dir.path().join(new_name)).unwrap();
+
+        let new_header_path = dir.path().join("new_data.sah");
+        let new_saf_path = dir.path().join("new_data.saf");
+        let mut new_header_file = std::fs::File::create(&new_header_path).unwrap();
+        let mut new_saf_file = std::fs::File::create(&new_saf_path).unwrap();
+        rebuilt
+            .rebuild_from(&saf_path, &mut new_header_file, &mut new_saf_file)
+            .unwrap();
+
+        let reopened = Filesystem::from_archive(&new_header_path).unwrap();
+        let dest_dir = dir.path().join("out");
+        reopened.extract_all(&new_saf_path, &dest_dir).unwrap();
+
+        assert_eq!(std::fs::read(dest_dir.join(name)).unwrap(), original);
+        assert_eq!(std::fs::read(dest_dir.join(new_name)).unwrap(), new_contents);
+
+        let results = reopened.verify(&new_saf_path, crate::io::VerifyOptions::default()).unwrap();
+        assert!(results.iter().all(|r| r.matches));
     }
 }
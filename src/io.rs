@@ -2,13 +2,85 @@ use crate::fs::{DirectoryEntry, File, Filesystem, FilesystemError, Folder};
 use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::{BufMut, BytesMut};
 use crc::{Crc, CRC_32_CKSUM};
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use digest::Digest;
+use encoding_rs::{Encoding, EUC_KR, UTF_8, WINDOWS_1252};
+use md5::Md5;
+use sha1::Sha1;
+use std::io::{Cursor, Read, Seek, SeekFrom, Take, Write};
+
+/// The text encoding used to read and write filenames stored in the archive header.
+///
+/// Real Shaiya data archives store Korean (and other multibyte) filenames in EUC-KR, so that is
+/// the default; UTF-8 and Windows-1252 are offered for archives produced by other tooling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// EUC-KR, the legacy Korean code page used by the original Shaiya client.
+    #[default]
+    EucKr,
+    Utf8,
+    Windows1252,
+}
+
+impl TextEncoding {
+    /// Returns the `encoding_rs` codec backing this encoding.
+    fn codec(self) -> &'static Encoding {
+        match self {
+            TextEncoding::EucKr => EUC_KR,
+            TextEncoding::Utf8 => UTF_8,
+            TextEncoding::Windows1252 => WINDOWS_1252,
+        }
+    }
+}
+
+/// The codec used to store a file's bytes in the data output.
+///
+/// `None` is the original, fully-compatible representation; other variants opt an archive into
+/// the extended header format (see [`HEADER_VERSION_EXTENDED`]) so that future codecs can coexist
+/// with archives written by older tooling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// The file's bytes are stored as-is.
+    #[default]
+    None,
+    /// The file's bytes are compressed with Zstandard.
+    Zstd,
+}
+
+impl Codec {
+    /// Returns the one-byte tag this codec is recorded as in the extended header format.
+    fn tag(self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Zstd => 1,
+        }
+    }
+
+    /// Resolves a codec from its one-byte tag.
+    ///
+    /// # Arguments
+    /// * `tag` - The tag read from the header.
+    fn from_tag(tag: u8) -> anyhow::Result<Self> {
+        match tag {
+            0 => Ok(Codec::None),
+            1 => Ok(Codec::Zstd),
+            other => Err(FilesystemError::UnknownCodec(other).into()),
+        }
+    }
+}
+
+/// The compression level used when a file is stored with `Codec::Zstd`.
+const ZSTD_COMPRESSION_LEVEL: i32 = 3;
 
 /// The magic identifier for the header file.
 pub const SAH_HEADER_MAGIC: &str = "SAH";
 
-/// The header format version.
-pub const HEADER_VERSION: u32 = 0;
+/// The original header format: per-file records have no codec tag, and every file's bytes are
+/// stored uncompressed.
+pub const HEADER_VERSION_LEGACY: u32 = 0;
+
+/// The extended header format: per-file records additionally carry a one-byte codec tag and the
+/// file's uncompressed size, allowing individual files to be stored with `Codec::Zstd`.
+pub const HEADER_VERSION_EXTENDED: u32 = 1;
 
 /// The name of the root directory.
 pub const ROOT_DIRECTORY_NAME: &str = "data";
@@ -16,51 +88,104 @@ pub const ROOT_DIRECTORY_NAME: &str = "data";
 /// The default capacity of a data.sah buffer (1mb)
 pub const DEFAULT_HEADER_CAPACITY: usize = 1_000_000;
 
-/// The default capacity of a data.saf buffer (2gb)
-pub const DEFAULT_DATA_CAPACITY: usize = 2_000_000_000; // 2gb
+/// The size of the chunks used to stream file contents into the data output, so that packing
+/// never needs to hold a whole file (let alone a whole archive) in memory at once.
+const COPY_CHUNK_SIZE: usize = 64 * 1_024;
 
-/// Builds the contents of the filesystem, into a header and data file. This allocates a 2gb buffer
-/// for the file data.
+/// Builds the contents of the filesystem, into a header and data file. File contents are streamed
+/// straight into the data output in fixed-size chunks, so packing a large tree does not require
+/// buffering it all in memory first.
 ///
 /// # Arguments
-/// * `fs`      - The virtual filesystem.
-/// * `header`  - The destination file for the header.
-/// * `data`    - The destination file for the data.
+/// * `fs`          - The virtual filesystem.
+/// * `header`      - The destination file for the header.
+/// * `data`        - The destination file for the data.
+/// * `encoding`    - The text encoding used for filenames.
+/// * `codec`       - The codec used to store file contents; `Codec::None` keeps the legacy header format, while any other codec opts the archive into the extended format.
 pub fn build_filesystem(
     fs: &Filesystem,
     header: &mut std::fs::File,
     data: &mut std::fs::File,
+    encoding: TextEncoding,
+    codec: Codec,
 ) -> anyhow::Result<()> {
+    let extended = codec != Codec::None;
     let mut header_buf = BytesMut::with_capacity(DEFAULT_HEADER_CAPACITY);
-    let mut data_buf = BytesMut::with_capacity(DEFAULT_DATA_CAPACITY);
-    let total_files = write_contents(&fs.contents, &mut header_buf, &mut data_buf)?;
+    let mut offset = 0u64;
+    let total_files = write_contents(&fs.contents, &mut header_buf, data, &mut offset, encoding, codec)?;
 
     let mut out = BytesMut::new();
     out.put_slice(SAH_HEADER_MAGIC.as_bytes());
-    out.put_u32_le(HEADER_VERSION);
+    out.put_u32_le(if extended { HEADER_VERSION_EXTENDED } else { HEADER_VERSION_LEGACY });
     out.put_u32_le(total_files);
     out.put_bytes(0, 40); // Unknown, assumed to be padding.
-    out.put_length_prefixed_string(ROOT_DIRECTORY_NAME);
+    out.put_length_prefixed_string(ROOT_DIRECTORY_NAME, encoding);
     out.put_slice(&header_buf);
     out.put_bytes(0, 8); // According to Parsec, the header should end with 8 null bytes (https://github.com/matigramirez/Parsec/blob/7c2e75f95bb5eaff45e22c2b30481a96a06a3016/src/Parsec/Shaiya/Data/Sah.cs#L183)
 
-    // Write the data to the files
+    // Write the header; the data file has already been written to directly.
     header.write_all(&out)?;
-    data.write_all(&data_buf)?;
     Ok(())
 }
 
-/// Serialize the contents of a directory to the header and data buffer.
+/// Builds the contents of the filesystem into a header file and a data payload split across
+/// sequentially-numbered parts, rolling to a new part once `part_size` bytes have been written to
+/// the current one. The header still records a single logical offset per file, so the split is
+/// transparent to anything reading the header.
+///
+/// # Arguments
+/// * `fs`              - The virtual filesystem.
+/// * `base_data_path`  - The path of the first data part (e.g. `data.saf`); subsequent parts are named `data.001`, `data.002`, and so on, alongside it.
+/// * `header`          - The destination file for the header.
+/// * `part_size`       - The maximum number of bytes written to each part before rolling to a new one.
+/// * `encoding`        - The text encoding used for filenames.
+/// * `codec`           - The codec used to store file contents.
+pub fn build_filesystem_split(
+    fs: &Filesystem,
+    base_data_path: &std::path::Path,
+    header: &mut std::fs::File,
+    part_size: u64,
+    encoding: TextEncoding,
+    codec: Codec,
+) -> anyhow::Result<()> {
+    let extended = codec != Codec::None;
+    let mut data = SplitDataWriter::create(base_data_path, part_size)?;
+    let mut header_buf = BytesMut::with_capacity(DEFAULT_HEADER_CAPACITY);
+    let mut offset = 0u64;
+    let total_files = write_contents(&fs.contents, &mut header_buf, &mut data, &mut offset, encoding, codec)?;
+
+    let mut out = BytesMut::new();
+    out.put_slice(SAH_HEADER_MAGIC.as_bytes());
+    out.put_u32_le(if extended { HEADER_VERSION_EXTENDED } else { HEADER_VERSION_LEGACY });
+    out.put_u32_le(total_files);
+    out.put_bytes(0, 40); // Unknown, assumed to be padding.
+    out.put_length_prefixed_string(ROOT_DIRECTORY_NAME, encoding);
+    out.put_slice(&header_buf);
+    out.put_bytes(0, 8); // According to Parsec, the header should end with 8 null bytes (https://github.com/matigramirez/Parsec/blob/7c2e75f95bb5eaff45e22c2b30481a96a06a3016/src/Parsec/Shaiya/Data/Sah.cs#L183)
+
+    header.write_all(&out)?;
+    Ok(())
+}
+
+/// Serialize the contents of a directory to the header buffer, streaming file data straight to
+/// the data output.
 ///
 /// # Arguments
 /// * `contents`    - The directory contents.
 /// * `header`      - The header destination.
 /// * `data`        - The data destination.
+/// * `offset`      - The current offset into the data output, advanced as files are written.
+/// * `encoding`    - The text encoding used for filenames.
+/// * `codec`       - The codec used to store each file's bytes.
 fn write_contents(
     contents: &[DirectoryEntry],
     header: &mut BytesMut,
-    data: &mut BytesMut,
+    data: &mut impl Write,
+    offset: &mut u64,
+    encoding: TextEncoding,
+    codec: Codec,
 ) -> anyhow::Result<u32> {
+    let extended = codec != Codec::None;
     let (files, folders): (Vec<_>, Vec<_>) = contents
         .iter()
         .partition(|e| matches!(e, DirectoryEntry::File(_)));
@@ -70,21 +195,241 @@ fn write_contents(
     for file in files {
         match file {
             DirectoryEntry::File(f) => {
-                if let File::Direct(path) = f {
-                    let file = std::fs::File::open(path)?;
-                    let metadata = file.metadata()?;
-                    let length = metadata.len() as u32;
-                    let name = path.file_name().unwrap().to_string_lossy().to_string();
+                if let File::Direct { name, source } = f {
+                    let mut file = std::fs::File::open(source)?;
+                    let uncompressed_length = file.metadata()?.len() as u32;
+
+                    header.put_length_prefixed_string(name, encoding);
+                    header.put_u64_le(*offset);
+
+                    let crc = match codec {
+                        Codec::None => {
+                            let crc = copy_with_checksum(&mut file, data, offset)?;
+                            header.put_u32_le(uncompressed_length);
+                            crc
+                        }
+                        Codec::Zstd => {
+                            let (crc, compressed_len) = compress_with_checksum(&mut file, data, offset)?;
+                            header.put_u32_le(compressed_len);
+                            crc
+                        }
+                    };
+                    header.put_u32_le(crc);
+
+                    if extended {
+                        header.put_u8(codec.tag());
+                        header.put_u32_le(uncompressed_length);
+                    }
+                }
+            }
+            _ => panic!("folder partitioned as file"),
+        }
+    }
+    header.put_u32_le((folders.len()) as u32);
+    for folder in folders {
+        match folder {
+            DirectoryEntry::Folder(f) => {
+                header.put_length_prefixed_string(&f.name, encoding);
+                total_files += write_contents(&f.contents, header, data, offset, encoding, codec)?;
+            }
+            _ => panic!("file partitioned as a folder"),
+        }
+    }
+    Ok(total_files)
+}
+
+/// Compresses `src`'s remaining bytes with Zstandard, streaming the compressed output straight
+/// into `data` rather than buffering it in memory, so packing a large file with `Codec::Zstd` does
+/// not lose the "never hold a whole file in memory" property `copy_with_checksum` gives the
+/// uncompressed path. Returns the compressed bytes' CRC-32/CKSUM and length, and advances `offset`
+/// by the number of compressed bytes written.
+///
+/// # Arguments
+/// * `src`     - The source to compress.
+/// * `data`    - The data destination.
+/// * `offset`  - The current offset into the data output, advanced as compressed bytes are written.
+fn compress_with_checksum(
+    src: &mut impl Read,
+    data: &mut impl Write,
+    offset: &mut u64,
+) -> anyhow::Result<(u32, u32)> {
+    let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
+    let mut sink = ChecksummingWriter { inner: data, digest: crc.digest(), len: 0 };
+
+    let mut encoder = zstd::Encoder::new(&mut sink, ZSTD_COMPRESSION_LEVEL)?;
+    std::io::copy(src, &mut encoder)?;
+    encoder.finish()?;
+
+    *offset += sink.len;
+    Ok((sink.digest.finalize(), sink.len as u32))
+}
+
+/// A `Write` adapter that incrementally computes the CRC-32/CKSUM of every byte written to it and
+/// counts them, while forwarding the bytes on to `inner` unchanged. Used to checksum a Zstandard
+/// encoder's compressed output as it streams out, without a second pass over the data.
+struct ChecksummingWriter<'a, 'digest, W: Write> {
+    inner: &'a mut W,
+    digest: crc::Digest<'digest, u32>,
+    len: u64,
+}
+
+impl<W: Write> Write for ChecksummingWriter<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.digest.update(buf);
+        self.inner.write_all(buf)?;
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
 
-                    header.put_length_prefixed_string(&name);
-                    header.put_u64_le(data.len() as u64);
-                    header.put_u32_le(length);
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Copies `src` into `data` in fixed-size chunks, incrementally computing the CRC-32/CKSUM of the
+/// copied bytes and advancing `offset` by the number of bytes written.
+///
+/// # Arguments
+/// * `src`     - The source file to copy.
+/// * `data`    - The data destination.
+/// * `offset`  - The current offset into the data output, advanced as bytes are written.
+fn copy_with_checksum(
+    src: &mut impl Read,
+    data: &mut impl Write,
+    offset: &mut u64,
+) -> anyhow::Result<u32> {
+    let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
+    let mut digest = crc.digest();
+    let mut buf = [0u8; COPY_CHUNK_SIZE];
+    loop {
+        let read = src.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+
+        digest.update(&buf[..read]);
+        data.write_all(&buf[..read])?;
+        *offset += read as u64;
+    }
+    Ok(digest.finalize())
+}
+
+/// Rebuilds an archive by streaming untouched virtual files straight from the old data file and
+/// appending new direct files, recomputing offsets and checksums as it goes. This lets callers
+/// apply `Filesystem::add_file`/`replace_file`/`remove_file`/`rename` edits without needing to
+/// fully unpack and repack the archive.
+///
+/// # Arguments
+/// * `fs`          - The (edited) filesystem to rebuild.
+/// * `old_saf`     - The path to the existing `data.saf` file backing `fs`'s virtual entries.
+/// * `header`      - The destination file for the rebuilt header.
+/// * `data`        - The destination file for the rebuilt data.
+/// * `encoding`    - The text encoding used for filenames.
+/// * `codec`       - The codec used to store any newly-added direct files; existing virtual files keep whichever codec they were already stored with.
+pub fn rebuild_filesystem(
+    fs: &Filesystem,
+    old_saf: &std::path::Path,
+    header: &mut std::fs::File,
+    data: &mut std::fs::File,
+    encoding: TextEncoding,
+    codec: Codec,
+) -> anyhow::Result<()> {
+    let extended = codec != Codec::None || any_compressed(&fs.contents);
+    let mut old_data = SafReader::open(old_saf)?;
+    let mut header_buf = BytesMut::with_capacity(DEFAULT_HEADER_CAPACITY);
+    let mut offset = 0u64;
+    let total_files = rebuild_contents(&fs.contents, &mut header_buf, data, &mut offset, &mut old_data, encoding, codec, extended)?;
 
-                    let file_data = std::fs::read(path)?;
-                    data.put_slice(&file_data);
+    let mut out = BytesMut::new();
+    out.put_slice(SAH_HEADER_MAGIC.as_bytes());
+    out.put_u32_le(if extended { HEADER_VERSION_EXTENDED } else { HEADER_VERSION_LEGACY });
+    out.put_u32_le(total_files);
+    out.put_bytes(0, 40); // Unknown, assumed to be padding.
+    out.put_length_prefixed_string(ROOT_DIRECTORY_NAME, encoding);
+    out.put_slice(&header_buf);
+    out.put_bytes(0, 8); // According to Parsec, the header should end with 8 null bytes (https://github.com/matigramirez/Parsec/blob/7c2e75f95bb5eaff45e22c2b30481a96a06a3016/src/Parsec/Shaiya/Data/Sah.cs#L183)
 
-                    let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
-                    header.put_u32_le(crc.checksum(&file_data));
+    header.write_all(&out)?;
+    Ok(())
+}
+
+/// Serialize the contents of a directory to the header buffer while rebuilding the data file,
+/// copying virtual files from `old_data` and direct files from disk.
+///
+/// # Arguments
+/// * `contents`    - The directory contents.
+/// * `header`      - The header destination.
+/// * `data`        - The data destination.
+/// * `offset`      - The current offset into the data output, advanced as files are written.
+/// * `old_data`    - A reader over the data file the virtual entries were parsed from.
+/// * `encoding`    - The text encoding used for filenames.
+/// * `codec`       - The codec used to store any newly-added direct files.
+/// * `extended`    - Whether the archive as a whole is using the extended header format.
+#[allow(clippy::too_many_arguments)]
+fn rebuild_contents(
+    contents: &[DirectoryEntry],
+    header: &mut BytesMut,
+    data: &mut impl Write,
+    offset: &mut u64,
+    old_data: &mut SafReader,
+    encoding: TextEncoding,
+    codec: Codec,
+    extended: bool,
+) -> anyhow::Result<u32> {
+    let (files, folders): (Vec<_>, Vec<_>) = contents
+        .iter()
+        .partition(|e| matches!(e, DirectoryEntry::File(_)));
+    let dir_file_qty = files.len() as u32;
+    let mut total_files = dir_file_qty;
+    header.put_u32_le(dir_file_qty);
+    for file in files {
+        match file {
+            DirectoryEntry::File(File::Direct { name, source }) => {
+                let mut src = std::fs::File::open(source)?;
+                let uncompressed_length = src.metadata()?.len() as u32;
+
+                header.put_length_prefixed_string(name, encoding);
+                header.put_u64_le(*offset);
+
+                let crc = match codec {
+                    Codec::None => {
+                        let crc = copy_with_checksum(&mut src, data, offset)?;
+                        header.put_u32_le(uncompressed_length);
+                        crc
+                    }
+                    Codec::Zstd => {
+                        let (crc, compressed_len) = compress_with_checksum(&mut src, data, offset)?;
+                        header.put_u32_le(compressed_len);
+                        crc
+                    }
+                };
+                header.put_u32_le(crc);
+
+                if extended {
+                    header.put_u8(codec.tag());
+                    header.put_u32_le(uncompressed_length);
+                }
+            }
+            DirectoryEntry::File(File::Virtual {
+                name,
+                offset: old_offset,
+                length,
+                codec: file_codec,
+                uncompressed_length,
+                ..
+            }) => {
+                let mut src = old_data.take_range(*old_offset, *length)?;
+
+                header.put_length_prefixed_string(name, encoding);
+                header.put_u64_le(*offset);
+                header.put_u32_le(*length);
+
+                let crc = copy_with_checksum(&mut src, data, offset)?;
+                header.put_u32_le(crc);
+
+                if extended {
+                    header.put_u8(file_codec.tag());
+                    header.put_u32_le(*uncompressed_length);
                 }
             }
             _ => panic!("folder partitioned as file"),
@@ -94,8 +439,8 @@ fn write_contents(
     for folder in folders {
         match folder {
             DirectoryEntry::Folder(f) => {
-                header.put_length_prefixed_string(&f.name);
-                total_files += write_contents(&f.contents, header, data)?;
+                header.put_length_prefixed_string(&f.name, encoding);
+                total_files += rebuild_contents(&f.contents, header, data, offset, old_data, encoding, codec, extended)?;
             }
             _ => panic!("file partitioned as a folder"),
         }
@@ -103,50 +448,86 @@ fn write_contents(
     Ok(total_files)
 }
 
+/// Returns `true` if any file in the tree (recursively) is stored with a codec other than
+/// `Codec::None`, so a rebuild that leaves such files untouched still opts into the extended
+/// header format.
+///
+/// # Arguments
+/// * `contents`    - The directory contents to scan.
+fn any_compressed(contents: &[DirectoryEntry]) -> bool {
+    contents.iter().any(|entry| match entry {
+        DirectoryEntry::File(File::Virtual { codec, .. }) => *codec != Codec::None,
+        DirectoryEntry::File(File::Direct { .. }) => false,
+        DirectoryEntry::Folder(f) => any_compressed(&f.contents),
+    })
+}
+
 /// Constructs a filesystem from an archive header.
 ///
 /// # Arguments
-/// * `header`  - The header buffer.
-pub fn read_filesystem(mut header: Cursor<&[u8]>) -> anyhow::Result<Filesystem> {
-    let magic = header.read_fixed_length_string(3)?;
+/// * `header`      - The header buffer.
+/// * `encoding`    - The text encoding used for filenames.
+pub fn read_filesystem(mut header: Cursor<&[u8]>, encoding: TextEncoding) -> anyhow::Result<Filesystem> {
+    let magic = header.read_fixed_length_string(3, encoding)?;
     if magic != SAH_HEADER_MAGIC {
         return Err(FilesystemError::InvalidMagicValue(magic).into());
     }
 
-    let _header_version = header.read_u32::<LittleEndian>()?;
+    let header_version = header.read_u32::<LittleEndian>()?;
+    let extended = match header_version {
+        HEADER_VERSION_LEGACY => false,
+        HEADER_VERSION_EXTENDED => true,
+        other => return Err(FilesystemError::UnsupportedHeaderVersion(other).into()),
+    };
     let _total_files = header.read_u32::<LittleEndian>()?;
     header.seek(SeekFrom::Current(40))?;
-    let _root_directory_name = header.read_length_prefixed_string()?;
+    let _root_directory_name = header.read_length_prefixed_string(encoding)?;
 
-    let contents = read_contents(&mut header)?;
+    let contents = read_contents(&mut header, encoding, extended)?;
     Ok(Filesystem { contents })
 }
 
 /// Read the contents of a directory from an archive header.
 ///
 /// # Arguments
-/// * `header`  - The archive header.
-fn read_contents(header: &mut Cursor<&[u8]>) -> anyhow::Result<Vec<DirectoryEntry>> {
+/// * `header`      - The archive header.
+/// * `encoding`    - The text encoding used for filenames.
+/// * `extended`    - Whether the header carries the extended per-file codec tag and uncompressed size fields.
+fn read_contents(
+    header: &mut Cursor<&[u8]>,
+    encoding: TextEncoding,
+    extended: bool,
+) -> anyhow::Result<Vec<DirectoryEntry>> {
     let mut contents = Vec::with_capacity(256);
     let dir_file_qty = header.read_u32::<LittleEndian>()?;
     for _ in 0..dir_file_qty {
-        let name = header.read_length_prefixed_string()?;
+        let name = header.read_length_prefixed_string(encoding)?;
         let offset = header.read_u64::<LittleEndian>()?;
         let length = header.read_u32::<LittleEndian>()?;
         let checksum = header.read_u32::<LittleEndian>()?;
 
+        let (codec, uncompressed_length) = if extended {
+            let codec = Codec::from_tag(header.read_u8()?)?;
+            let uncompressed_length = header.read_u32::<LittleEndian>()?;
+            (codec, uncompressed_length)
+        } else {
+            (Codec::None, length)
+        };
+
         contents.push(DirectoryEntry::File(File::Virtual {
             name,
             offset,
             length,
             checksum,
+            codec,
+            uncompressed_length,
         }));
     }
 
     let folder_qty = header.read_u32::<LittleEndian>()?;
     for _ in 0..folder_qty {
-        let name = header.read_length_prefixed_string()?;
-        let folder_contents = read_contents(header)?;
+        let name = header.read_length_prefixed_string(encoding)?;
+        let folder_contents = read_contents(header, encoding, extended)?;
 
         contents.push(DirectoryEntry::Folder(Folder {
             name,
@@ -156,35 +537,501 @@ fn read_contents(header: &mut Cursor<&[u8]>) -> anyhow::Result<Vec<DirectoryEntr
     Ok(contents)
 }
 
+/// Provides random access reads of individual files out of a `data.saf` file, seeking to each
+/// file's stored offset rather than loading the whole archive into memory.
+pub struct SafReader {
+    data: std::fs::File,
+}
+
+impl SafReader {
+    /// Opens a `data.saf` file for random-access reads.
+    ///
+    /// # Arguments
+    /// * `data_path`   - The path to the data file.
+    pub fn open(data_path: &std::path::Path) -> anyhow::Result<Self> {
+        let data = std::fs::File::open(data_path)?;
+        Ok(Self { data })
+    }
+
+    /// Reads a virtual file's bytes out of the data file.
+    ///
+    /// # Arguments
+    /// * `file`    - The virtual file entry to read.
+    pub fn read_file(&mut self, file: &File) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.read_file_into(file, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Streams a virtual file's bytes out of the data file, into the given writer.
+    ///
+    /// # Arguments
+    /// * `file`    - The virtual file entry to read.
+    /// * `writer`  - The destination to write the file's bytes to.
+    pub fn read_file_into(&mut self, file: &File, writer: &mut impl Write) -> anyhow::Result<()> {
+        let (offset, length, codec) = match file {
+            File::Virtual { offset, length, codec, .. } => (*offset, *length, *codec),
+            File::Direct { source, .. } => return Err(FilesystemError::NotAVirtualFile(source.clone()).into()),
+        };
+
+        let mut chunk = self.take_range(offset, length)?;
+        match codec {
+            Codec::None => {
+                std::io::copy(&mut chunk, writer)?;
+            }
+            Codec::Zstd => {
+                zstd::stream::copy_decode(chunk, writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the `length` bytes at `offset` and recomputes their CRC-32/CKSUM, along with any
+    /// digests requested by `opts`, without buffering the whole range up front.
+    ///
+    /// The CRC is always taken over the stored bytes, matching the checksum recorded in the
+    /// header, which is likewise computed over the stored (possibly compressed) bytes. The MD5 and
+    /// SHA-1 digests, however, are meant for cross-checking a file's *original* contents against an
+    /// external manifest, so for a `Codec::Zstd` file they are computed over the decompressed bytes
+    /// instead, at the cost of a second pass over the range when either is requested.
+    ///
+    /// Errors with [`FilesystemError::TruncatedRead`] rather than returning a digest if fewer than
+    /// `length` bytes are actually available at `offset` (e.g. a corrupt header recording an offset
+    /// or length past the end of the data file), so a truncated read is never mistaken for a
+    /// checksum mismatch.
+    ///
+    /// # Arguments
+    /// * `offset`  - The offset of the file's bytes within the data file.
+    /// * `length`  - The number of bytes to read.
+    /// * `codec`   - The codec the file's bytes are stored with.
+    /// * `opts`    - Which additional digests to compute.
+    pub fn verify_file(&mut self, offset: u64, length: u32, codec: Codec, opts: VerifyOptions) -> anyhow::Result<FileDigest> {
+        let mut chunk = self.take_range(offset, length)?;
+
+        let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
+        let mut crc_digest = crc.digest();
+        let mut plain_md5 = (codec == Codec::None && opts.md5).then(Md5::new);
+        let mut plain_sha1 = (codec == Codec::None && opts.sha1).then(Sha1::new);
+
+        let mut total_read = 0u64;
+        let mut buf = [0u8; COPY_CHUNK_SIZE];
+        loop {
+            let read = chunk.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+
+            total_read += read as u64;
+            crc_digest.update(&buf[..read]);
+            if let Some(md5) = plain_md5.as_mut() {
+                md5.update(&buf[..read]);
+            }
+            if let Some(sha1) = plain_sha1.as_mut() {
+                sha1.update(&buf[..read]);
+            }
+        }
+
+        if total_read != length as u64 {
+            return Err(FilesystemError::TruncatedRead { offset, expected: length, actual: total_read }.into());
+        }
+
+        let (md5, sha1) = if codec == Codec::Zstd && (opts.md5 || opts.sha1) {
+            let compressed = self.take_range(offset, length)?;
+            let mut sink = DigestSink { md5: opts.md5.then(Md5::new), sha1: opts.sha1.then(Sha1::new) };
+            zstd::stream::copy_decode(compressed, &mut sink)?;
+            (sink.md5.map(|d| hex_digest(&d.finalize())), sink.sha1.map(|d| hex_digest(&d.finalize())))
+        } else {
+            (plain_md5.map(|d| hex_digest(&d.finalize())), plain_sha1.map(|d| hex_digest(&d.finalize())))
+        };
+
+        Ok(FileDigest { checksum: crc_digest.finalize(), md5, sha1 })
+    }
+
+    /// Seeks to `offset` and returns a reader bounded to the following `length` bytes.
+    ///
+    /// # Arguments
+    /// * `offset`  - The offset to seek to.
+    /// * `length`  - The number of bytes the returned reader is bounded to.
+    fn take_range(&mut self, offset: u64, length: u32) -> anyhow::Result<Take<&std::fs::File>> {
+        self.data.seek(SeekFrom::Start(offset))?;
+        Ok((&self.data).take(length as u64))
+    }
+}
+
+/// Returns the path of the `part_index`th data part alongside `base_path`: the base path itself
+/// for part 0, otherwise the base path with its extension replaced by a zero-padded part number
+/// (e.g. `data.saf` -> `data.001`).
+///
+/// # Arguments
+/// * `base_path`   - The path of the first data part.
+/// * `part_index`  - The index of the part to resolve a path for.
+fn part_path(base_path: &std::path::Path, part_index: u32) -> std::path::PathBuf {
+    if part_index == 0 {
+        base_path.to_path_buf()
+    } else {
+        base_path.with_extension(format!("{part_index:03}"))
+    }
+}
+
+/// Writes a data payload across sequentially-numbered parts, rolling to a new part once
+/// `part_size` bytes have been written to the current one.
+pub struct SplitDataWriter {
+    base_path: std::path::PathBuf,
+    part_size: u64,
+    part_index: u32,
+    part_file: std::fs::File,
+    bytes_in_part: u64,
+}
+
+impl SplitDataWriter {
+    /// Creates the first data part at `base_path`, ready to have subsequent parts rolled out
+    /// alongside it as `part_size` is reached.
+    ///
+    /// # Arguments
+    /// * `base_path`   - The path of the first data part (e.g. `data.saf`).
+    /// * `part_size`   - The maximum number of bytes written to each part before rolling to a new one.
+    pub fn create(base_path: &std::path::Path, part_size: u64) -> anyhow::Result<Self> {
+        let part_file = std::fs::File::create(base_path)?;
+        Ok(Self {
+            base_path: base_path.to_path_buf(),
+            part_size,
+            part_index: 0,
+            part_file,
+            bytes_in_part: 0,
+        })
+    }
+
+    /// Closes the current part and opens the next one.
+    fn roll_part(&mut self) -> std::io::Result<()> {
+        self.part_index += 1;
+        self.part_file = std::fs::File::create(part_path(&self.base_path, self.part_index))?;
+        self.bytes_in_part = 0;
+        Ok(())
+    }
+}
+
+impl Write for SplitDataWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut total_written = 0;
+        while total_written < buf.len() {
+            if self.part_size > 0 && self.bytes_in_part >= self.part_size {
+                self.roll_part()?;
+            }
+
+            // Never write more of `buf` than fits in the remainder of the current part, so a
+            // single `write` call can straddle as many part boundaries as needed and every part
+            // but the last ends up exactly `part_size` bytes.
+            let remaining_in_part = if self.part_size > 0 {
+                (self.part_size - self.bytes_in_part) as usize
+            } else {
+                buf.len() - total_written
+            };
+            let end = total_written + remaining_in_part.min(buf.len() - total_written);
+
+            let written = self.part_file.write(&buf[total_written..end])?;
+            self.bytes_in_part += written as u64;
+            total_written += written;
+
+            if written == 0 {
+                break;
+            }
+        }
+        Ok(total_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.part_file.flush()
+    }
+}
+
+/// Provides random access reads across a data payload split into sequentially-numbered parts,
+/// mapping a logical offset to the `(part_index, offset_within_part)` it falls in and
+/// transparently reading across a part boundary when a file straddles two parts.
+///
+/// Part boundaries are derived from each part's actual length on disk rather than a caller-supplied
+/// `part_size`, since the part size used at build time is not recorded anywhere a reader could
+/// independently check it; this also means the reader works regardless of how evenly `SplitDataWriter`
+/// happened to fill each part.
+pub struct SplitSafReader {
+    parts: Vec<std::fs::File>,
+    /// The logical offset each part starts at, i.e. the cumulative length of every earlier part.
+    part_starts: Vec<u64>,
+    /// Each part's length on disk, parallel to `parts` and `part_starts`.
+    part_lengths: Vec<u64>,
+}
+
+impl SplitSafReader {
+    /// Opens a split data payload for random-access reads, discovering parts alongside
+    /// `base_data_path` until one is missing.
+    ///
+    /// # Arguments
+    /// * `base_data_path`  - The path of the first data part (e.g. `data.saf`).
+    pub fn open(base_data_path: &std::path::Path) -> anyhow::Result<Self> {
+        let mut parts = vec![std::fs::File::open(base_data_path)?];
+        let mut part_index = 1u32;
+        while let Ok(part_file) = std::fs::File::open(part_path(base_data_path, part_index)) {
+            parts.push(part_file);
+            part_index += 1;
+        }
+
+        let mut part_starts = Vec::with_capacity(parts.len());
+        let mut part_lengths = Vec::with_capacity(parts.len());
+        let mut logical_offset = 0u64;
+        for part in &parts {
+            let length = part.metadata()?.len();
+            part_starts.push(logical_offset);
+            part_lengths.push(length);
+            logical_offset += length;
+        }
+
+        Ok(Self { parts, part_starts, part_lengths })
+    }
+
+    /// Reads a virtual file's bytes out of the split data payload.
+    ///
+    /// # Arguments
+    /// * `file`    - The virtual file entry to read.
+    pub fn read_file(&mut self, file: &File) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.read_file_into(file, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Streams a virtual file's bytes out of the split data payload, into the given writer,
+    /// transparently crossing a part boundary if the file straddles two parts.
+    ///
+    /// # Arguments
+    /// * `file`    - The virtual file entry to read.
+    /// * `writer`  - The destination to write the file's bytes to.
+    pub fn read_file_into(&mut self, file: &File, writer: &mut impl Write) -> anyhow::Result<()> {
+        let (offset, length, codec) = match file {
+            File::Virtual { offset, length, codec, .. } => (*offset, *length, *codec),
+            File::Direct { source, .. } => return Err(FilesystemError::NotAVirtualFile(source.clone()).into()),
+        };
+
+        match codec {
+            Codec::None => self.copy_range(offset, length, writer),
+            Codec::Zstd => {
+                let mut compressed = Vec::new();
+                self.copy_range(offset, length, &mut compressed)?;
+                zstd::stream::copy_decode(Cursor::new(compressed), writer)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads the `length` bytes at the logical `offset` and recomputes their CRC-32/CKSUM, along
+    /// with any digests requested by `opts`, reading across a part boundary when the range
+    /// straddles two parts. Mirrors `SafReader::verify_file`; see its docs for how the CRC and the
+    /// optional MD5/SHA-1 digests relate to `codec`.
+    ///
+    /// # Arguments
+    /// * `offset`  - The logical offset of the file's bytes within the (unsplit) data payload.
+    /// * `length`  - The number of bytes to read.
+    /// * `codec`   - The codec the file's bytes are stored with.
+    /// * `opts`    - Which additional digests to compute.
+    pub fn verify_file(&mut self, offset: u64, length: u32, codec: Codec, opts: VerifyOptions) -> anyhow::Result<FileDigest> {
+        let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
+        let mut sink = VerifyingWriter {
+            digest: crc.digest(),
+            md5: (codec == Codec::None && opts.md5).then(Md5::new),
+            sha1: (codec == Codec::None && opts.sha1).then(Sha1::new),
+        };
+        self.copy_range(offset, length, &mut sink)?;
+        let checksum = sink.digest.finalize();
+
+        let (md5, sha1) = if codec == Codec::Zstd && (opts.md5 || opts.sha1) {
+            let mut compressed = Vec::new();
+            self.copy_range(offset, length, &mut compressed)?;
+            let mut decoded = DigestSink { md5: opts.md5.then(Md5::new), sha1: opts.sha1.then(Sha1::new) };
+            zstd::stream::copy_decode(Cursor::new(compressed), &mut decoded)?;
+            (decoded.md5.map(|d| hex_digest(&d.finalize())), decoded.sha1.map(|d| hex_digest(&d.finalize())))
+        } else {
+            (sink.md5.map(|d| hex_digest(&d.finalize())), sink.sha1.map(|d| hex_digest(&d.finalize())))
+        };
+
+        Ok(FileDigest { checksum, md5, sha1 })
+    }
+
+    /// Copies `length` bytes starting at the logical `offset` into `writer`, reading across a
+    /// part boundary when the range straddles two parts.
+    ///
+    /// Errors with [`FilesystemError::TruncatedRead`] rather than underflowing or silently copying
+    /// fewer bytes if `offset`/`length` run past the end of the last part (e.g. a corrupt header).
+    ///
+    /// # Arguments
+    /// * `offset`  - The logical offset of the range within the (unsplit) data payload.
+    /// * `length`  - The number of bytes to copy.
+    /// * `writer`  - The destination to write the copied bytes to.
+    fn copy_range(&mut self, offset: u64, length: u32, writer: &mut impl Write) -> anyhow::Result<()> {
+        let mut remaining = length as u64;
+        let mut logical_offset = offset;
+        while remaining > 0 {
+            let (part_index, offset_in_part) = self.locate(logical_offset)?;
+            let part_length = self.part_lengths[part_index as usize];
+            let available = part_length.checked_sub(offset_in_part).filter(|available| *available > 0);
+            let Some(available) = available else {
+                let actual = length as u64 - remaining;
+                return Err(FilesystemError::TruncatedRead { offset, expected: length, actual }.into());
+            };
+
+            let part = self
+                .parts
+                .get_mut(part_index as usize)
+                .ok_or(FilesystemError::PartNotFound(part_index))?;
+            part.seek(SeekFrom::Start(offset_in_part))?;
+
+            let to_read = remaining.min(available);
+            let mut chunk = part.take(to_read);
+            let copied = std::io::copy(&mut chunk, writer)?;
+            if copied != to_read {
+                let actual = length as u64 - remaining + copied;
+                return Err(FilesystemError::TruncatedRead { offset, expected: length, actual }.into());
+            }
+
+            logical_offset += to_read;
+            remaining -= to_read;
+        }
+        Ok(())
+    }
+
+    /// Maps a logical offset into the data payload to the part index and offset within that part
+    /// it falls in, by finding the last part whose recorded start is at or before `logical_offset`.
+    ///
+    /// # Arguments
+    /// * `logical_offset`  - The offset within the (unsplit) data payload.
+    fn locate(&self, logical_offset: u64) -> anyhow::Result<(u32, u64)> {
+        let part_index = match self.part_starts.partition_point(|start| *start <= logical_offset) {
+            0 => return Err(FilesystemError::PartNotFound(0).into()),
+            index => index - 1,
+        };
+        let offset_in_part = logical_offset - self.part_starts[part_index];
+        Ok((part_index as u32, offset_in_part))
+    }
+}
+
+/// Options controlling how archive integrity is verified.
+///
+/// # Arguments
+/// * `md5`         - Additionally compute an MD5 digest for each file.
+/// * `sha1`        - Additionally compute a SHA-1 digest for each file.
+/// * `parallel`    - Verify files concurrently, since each check is an independent seek + range read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VerifyOptions {
+    pub md5: bool,
+    pub sha1: bool,
+    pub parallel: bool,
+}
+
+/// The digests recomputed for a single file by `SafReader::verify_file`.
+#[derive(Debug, Clone)]
+pub struct FileDigest {
+    /// The recomputed CRC-32/CKSUM.
+    pub checksum: u32,
+    /// The recomputed MD5 digest, as a lowercase hex string, if requested.
+    pub md5: Option<String>,
+    /// The recomputed SHA-1 digest, as a lowercase hex string, if requested.
+    pub sha1: Option<String>,
+}
+
+/// Formats a digest's raw bytes as a lowercase hex string.
+///
+/// # Arguments
+/// * `bytes`   - The digest bytes to format.
+fn hex_digest(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+/// A `Write` sink that feeds every byte written to it into whichever of MD5 / SHA-1 are present,
+/// discarding the bytes otherwise. Used to hash a `zstd` decode's output stream directly, without
+/// buffering the decompressed file in memory first.
+struct DigestSink {
+    md5: Option<Md5>,
+    sha1: Option<Sha1>,
+}
+
+impl Write for DigestSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(md5) = self.md5.as_mut() {
+            md5.update(buf);
+        }
+        if let Some(sha1) = self.sha1.as_mut() {
+            sha1.update(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A `Write` sink that feeds every byte written to it into a CRC-32/CKSUM digest and, optionally,
+/// MD5 / SHA-1, discarding the bytes otherwise. Used by `SplitSafReader::verify_file` to hash a
+/// file's bytes as `copy_range` streams them across part boundaries, without buffering the range
+/// up front.
+struct VerifyingWriter<'digest> {
+    digest: crc::Digest<'digest, u32>,
+    md5: Option<Md5>,
+    sha1: Option<Sha1>,
+}
+
+impl Write for VerifyingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.digest.update(buf);
+        if let Some(md5) = self.md5.as_mut() {
+            md5.update(buf);
+        }
+        if let Some(sha1) = self.sha1.as_mut() {
+            sha1.update(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub trait ShaiyaWrite {
     /// Writes a null-terminated string, where the string is prefixed
     /// with it's length as a little-endian u32.
     ///
     /// # Arguments
-    /// * `string`  - The string to write.
-    fn put_length_prefixed_string(&mut self, string: &str);
+    /// * `string`      - The string to write.
+    /// * `encoding`    - The text encoding to encode the string with.
+    fn put_length_prefixed_string(&mut self, string: &str, encoding: TextEncoding);
 }
 
 pub trait ShaiyaRead {
     /// Reads a string with a fixed number of bytes.
     ///
     /// # Arguments
-    /// * `length`  - The number of bytes to read.
-    fn read_fixed_length_string(&mut self, length: usize) -> anyhow::Result<String>;
+    /// * `length`      - The number of bytes to read.
+    /// * `encoding`    - The text encoding to decode the bytes with.
+    fn read_fixed_length_string(&mut self, length: usize, encoding: TextEncoding) -> anyhow::Result<String>;
 
     /// Reads a null-terminated string, where the string is prefixed
     /// with it's length as a little-endian u32.
-    fn read_length_prefixed_string(&mut self) -> anyhow::Result<String>;
+    ///
+    /// # Arguments
+    /// * `encoding`    - The text encoding to decode the bytes with.
+    fn read_length_prefixed_string(&mut self, encoding: TextEncoding) -> anyhow::Result<String>;
 }
 
 impl<T> ShaiyaWrite for T
 where
     T: BufMut,
 {
-    fn put_length_prefixed_string(&mut self, string: &str) {
-        let bytes = string.as_bytes();
+    fn put_length_prefixed_string(&mut self, string: &str, encoding: TextEncoding) {
+        let (bytes, _, _) = encoding.codec().encode(string);
+        // The stored length prefix is the *encoded* byte length, plus one for the trailing NUL.
         self.put_u32_le((bytes.len() + 1) as u32);
-        self.put_slice(bytes);
+        self.put_slice(&bytes);
         self.put_u8(0);
     }
 }
@@ -193,19 +1040,21 @@ impl<T> ShaiyaRead for T
 where
     T: Read,
 {
-    fn read_fixed_length_string(&mut self, length: usize) -> anyhow::Result<String> {
-        let mut string = String::with_capacity(length);
-        for _ in 0..length {
-            let ch = self.read_u8()? as char;
-            if ch != '\0' {
-                string.push(ch)
-            }
+    fn read_fixed_length_string(&mut self, length: usize, encoding: TextEncoding) -> anyhow::Result<String> {
+        let mut bytes = vec![0u8; length];
+        self.read_exact(&mut bytes)?;
+
+        // Strip the trailing NUL terminator(s) before decoding, as they aren't part of the text.
+        while matches!(bytes.last(), Some(0)) {
+            bytes.pop();
         }
-        Ok(string)
+
+        let (decoded, _, _) = encoding.codec().decode(&bytes);
+        Ok(decoded.into_owned())
     }
 
-    fn read_length_prefixed_string(&mut self) -> anyhow::Result<String> {
+    fn read_length_prefixed_string(&mut self, encoding: TextEncoding) -> anyhow::Result<String> {
         let length = self.read_u32::<LittleEndian>()? as usize;
-        self.read_fixed_length_string(length)
+        self.read_fixed_length_string(length, encoding)
     }
 }